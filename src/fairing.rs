@@ -1,35 +1,114 @@
 use lazy_static::lazy_static;
+use lru::LruCache;
 use rocket::{
     fairing::{Fairing, Info, Kind},
     http::{hyper::header::{CONTENT_ENCODING, CACHE_CONTROL}, Header, MediaType},
     tokio::{
         io::{AsyncRead, ReadBuf},
-        sync::RwLock,
+        sync::Mutex,
     },
     Request, Response,
 };
-use std::{collections::HashMap, io::Cursor, task::Poll};
+use std::{io::Cursor, num::NonZeroUsize, sync::Arc, task::Poll};
 
-use crate::{CompressionUtils, Encoding};
+use crate::{CompressionOptions, CompressionUtils, Encoding, Level};
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub(crate) enum CachedEncoding {
-    Gzip,
+    #[cfg(feature = "brotli")]
     Brotli,
+    #[cfg(feature = "zstd")]
+    Zstd,
+    #[cfg(feature = "gzip")]
+    Gzip,
+    #[cfg(feature = "deflate")]
+    Deflate,
+}
+
+impl From<Encoding> for CachedEncoding {
+    fn from(encoding: Encoding) -> Self {
+        match encoding {
+            #[cfg(feature = "brotli")]
+            Encoding::Brotli => CachedEncoding::Brotli,
+            #[cfg(feature = "zstd")]
+            Encoding::Zstd => CachedEncoding::Zstd,
+            #[cfg(feature = "gzip")]
+            Encoding::Gzip => CachedEncoding::Gzip,
+            #[cfg(feature = "deflate")]
+            Encoding::Deflate => CachedEncoding::Deflate,
+        }
+    }
 }
 
 lazy_static! {
-    static ref EXCLUSIONS: Vec<MediaType> = vec![
+    /// Content types left uncompressed by default, before any `exclude`
+    /// calls on a builder add to the list.
+    static ref DEFAULT_EXCLUSIONS: Vec<MediaType> = vec![
         MediaType::parse_flexible("application/gzip").unwrap(),
         MediaType::parse_flexible("application/zip").unwrap(),
         MediaType::parse_flexible("image/*").unwrap(),
         MediaType::parse_flexible("video/*").unwrap(),
         MediaType::parse_flexible("application/octet-stream").unwrap(),
     ];
-    static ref CACHED_FILES: RwLock<HashMap<(String, CachedEncoding), &'static [u8]>> = {
-        let m = HashMap::new();
-        RwLock::new(m)
-    };
+}
+
+impl Default for CompressionOptions {
+    fn default() -> Self {
+        CompressionOptions {
+            exclusions: DEFAULT_EXCLUSIONS.clone(),
+            min_size: 0,
+            level: Level::Default,
+        }
+    }
+}
+
+/// Default cap on the total size of cached compressed bodies, in bytes.
+const DEFAULT_MAX_CACHE_BYTES: usize = 64 * 1024 * 1024;
+
+/// Default cap on the number of cached compressed bodies.
+const DEFAULT_MAX_CACHE_ENTRIES: usize = 1024;
+
+/// A bounded, least-recently-used cache of compressed response bodies,
+/// evicting by entry count (via the underlying [`LruCache`]) and by total
+/// byte size (tracked alongside it).
+struct ResponseCache {
+    entries: LruCache<(String, CachedEncoding), Arc<[u8]>>,
+    total_bytes: usize,
+    max_bytes: usize,
+}
+
+impl ResponseCache {
+    fn new(max_bytes: usize, max_entries: usize) -> Self {
+        ResponseCache {
+            entries: LruCache::new(NonZeroUsize::new(max_entries.max(1)).unwrap()),
+            total_bytes: 0,
+            max_bytes,
+        }
+    }
+
+    /// Looks up `key`, marking it as most-recently-used on a hit.
+    fn get(&mut self, key: &(String, CachedEncoding)) -> Option<Arc<[u8]>> {
+        self.entries.get(key).cloned()
+    }
+
+    /// Inserts `value` under `key`, then evicts least-recently-used entries
+    /// until the cache is back within its byte budget.
+    fn insert(&mut self, key: (String, CachedEncoding), value: Arc<[u8]>) {
+        self.total_bytes += value.len();
+        // `push`, unlike `put`, also returns the entry evicted to make room
+        // when `key` is new and the cache is already at its entry cap —
+        // without it that entry's bytes would never be subtracted back out.
+        if let Some((_, replaced)) = self.entries.push(key, value) {
+            self.total_bytes -= replaced.len();
+        }
+
+        while self.total_bytes > self.max_bytes {
+            match self.entries.pop_lru() {
+                Some((_, evicted)) => self.total_bytes -= evicted.len(),
+                None => break,
+            }
+        }
+    }
 }
 
 /// Compresses all responses with Brotli or Gzip compression.
@@ -63,7 +142,9 @@ lazy_static! {
 ///     # ;
 ///
 /// ```
-pub struct Compression(());
+pub struct Compression {
+    options: CompressionOptions,
+}
 
 impl Compression {
     /// Returns a fairing that compresses outgoing requests.
@@ -83,7 +164,75 @@ impl Compression {
     ///     # ;
     /// ```
     pub fn fairing() -> Compression {
-        Compression(())
+        Self::builder().build()
+    }
+
+    /// Returns a [`CompressionBuilder`] for configuring the exclusion list,
+    /// minimum-size threshold, and compression level before building the
+    /// fairing.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    ///
+    /// use rocket::http::MediaType;
+    /// use rocket_async_compression::{Compression, Level};
+    ///
+    /// rocket::build()
+    ///     // ...
+    ///     .attach(
+    ///         Compression::builder()
+    ///             .exclude(MediaType::parse_flexible("application/wasm").unwrap())
+    ///             .min_size(1024)
+    ///             .level(Level::Best)
+    ///             .build(),
+    ///     )
+    ///     // ...
+    ///     # ;
+    /// ```
+    pub fn builder() -> CompressionBuilder {
+        CompressionBuilder {
+            options: CompressionOptions::default(),
+        }
+    }
+}
+
+/// Builds a [`Compression`] fairing with a customized exclusion list,
+/// minimum-size threshold, and compression level.
+///
+/// Created via [`Compression::builder`].
+pub struct CompressionBuilder {
+    options: CompressionOptions,
+}
+
+impl CompressionBuilder {
+    /// Adds `media_type` to the set of content types left uncompressed, on
+    /// top of the default exclusion list (`application/gzip`,
+    /// `application/zip`, `image/*`, `video/*`, `application/octet-stream`).
+    pub fn exclude(mut self, media_type: MediaType) -> Self {
+        self.options.exclusions.push(media_type);
+        self
+    }
+
+    /// Skips compression for responses whose body is smaller than `bytes`,
+    /// since small payloads often grow once compressed. Bodies whose size
+    /// isn't known up front are always compressed.
+    pub fn min_size(mut self, bytes: usize) -> Self {
+        self.options.min_size = bytes;
+        self
+    }
+
+    /// Sets the compression level passed through to the underlying codec.
+    pub fn level(mut self, level: Level) -> Self {
+        self.options.level = level;
+        self
+    }
+
+    /// Finishes building, returning the configured [`Compression`] fairing.
+    pub fn build(self) -> Compression {
+        Compression {
+            options: self.options,
+        }
     }
 }
 
@@ -97,17 +246,20 @@ impl Fairing for Compression {
     }
 
     async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
-        super::CompressionUtils::compress_response(request, response, &EXCLUSIONS);
+        super::CompressionUtils::compress_response(request, response, &self.options);
     }
 }
 
 /// Compresses all responses with Brotli or Gzip compression. Caches compressed
 /// response bodies in memory for selected file types/path suffixes, useful for
-/// compressing large compiled JS/CSS files, OTF font packs, etc.  Note that all
-/// cached files are held in memory indefinitely.
+/// compressing large compiled JS/CSS files, OTF font packs, etc. The cache is
+/// bounded by both a total byte budget and an entry count, evicting the
+/// least-recently-used entry first once either limit is reached.
 ///
 /// Compression is done in the same manner as the [`Compression`](Compression)
-/// fairing.
+/// fairing. A freshly compressed (non-cached) response is also given a
+/// `Cache-Control: max-age=31536000` header, unless the route already set
+/// one, since cached bodies are meant to be served for a long time.
 ///
 /// # Usage
 ///
@@ -127,12 +279,99 @@ impl Fairing for Compression {
 /// ```
 pub struct CachedCompression {
     pub cached_path_endings: Vec<&'static str>,
+    cache: Mutex<ResponseCache>,
+    options: CompressionOptions,
 }
 
 impl CachedCompression {
+    /// Returns a fairing that caches compressed bodies under
+    /// [`DEFAULT_MAX_CACHE_BYTES`] and [`DEFAULT_MAX_CACHE_ENTRIES`] limits.
     pub fn fairing(cached_path_endings: Vec<&'static str>) -> CachedCompression {
-        CachedCompression {
+        Self::builder(cached_path_endings).build()
+    }
+
+    /// Returns a fairing that caches compressed bodies, evicting
+    /// least-recently-used entries once the cache holds more than
+    /// `max_bytes` total bytes or `max_entries` entries.
+    pub fn fairing_with_limits(
+        cached_path_endings: Vec<&'static str>,
+        max_bytes: usize,
+        max_entries: usize,
+    ) -> CachedCompression {
+        Self::builder(cached_path_endings)
+            .max_bytes(max_bytes)
+            .max_entries(max_entries)
+            .build()
+    }
+
+    /// Returns a [`CachedCompressionBuilder`] for configuring the cache
+    /// limits, exclusion list, minimum-size threshold, and compression
+    /// level before building the fairing.
+    pub fn builder(cached_path_endings: Vec<&'static str>) -> CachedCompressionBuilder {
+        CachedCompressionBuilder {
             cached_path_endings,
+            max_bytes: DEFAULT_MAX_CACHE_BYTES,
+            max_entries: DEFAULT_MAX_CACHE_ENTRIES,
+            options: CompressionOptions::default(),
+        }
+    }
+}
+
+/// Builds a [`CachedCompression`] fairing with customized cache limits,
+/// exclusion list, minimum-size threshold, and compression level.
+///
+/// Created via [`CachedCompression::builder`].
+pub struct CachedCompressionBuilder {
+    cached_path_endings: Vec<&'static str>,
+    max_bytes: usize,
+    max_entries: usize,
+    options: CompressionOptions,
+}
+
+impl CachedCompressionBuilder {
+    /// Sets the cache's total byte budget, evicting least-recently-used
+    /// entries once exceeded.
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Sets the cache's maximum entry count, evicting least-recently-used
+    /// entries once exceeded.
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Adds `media_type` to the set of content types left uncompressed, on
+    /// top of the default exclusion list (`application/gzip`,
+    /// `application/zip`, `image/*`, `video/*`, `application/octet-stream`).
+    pub fn exclude(mut self, media_type: MediaType) -> Self {
+        self.options.exclusions.push(media_type);
+        self
+    }
+
+    /// Skips compression for responses whose body is smaller than `bytes`,
+    /// since small payloads often grow once compressed. Bodies whose size
+    /// isn't known up front are always compressed.
+    pub fn min_size(mut self, bytes: usize) -> Self {
+        self.options.min_size = bytes;
+        self
+    }
+
+    /// Sets the compression level passed through to the underlying codec.
+    pub fn level(mut self, level: Level) -> Self {
+        self.options.level = level;
+        self
+    }
+
+    /// Finishes building, returning the configured [`CachedCompression`]
+    /// fairing.
+    pub fn build(self) -> CachedCompression {
+        CachedCompression {
+            cached_path_endings: self.cached_path_endings,
+            cache: Mutex::new(ResponseCache::new(self.max_bytes, self.max_entries)),
+            options: self.options,
         }
     }
 }
@@ -149,7 +388,7 @@ impl AsyncRead for ErrorBody {
     ) -> Poll<Result<(), std::io::Error>> {
         let err = match self.0.take() {
             Some(err) => err,
-            None => std::io::Error::new(std::io::ErrorKind::Other, "ErrorBody already read"),
+            None => std::io::Error::other("ErrorBody already read"),
         };
         Poll::Ready(Err(err))
     }
@@ -171,52 +410,32 @@ impl Fairing for CachedCompression {
             return;
         }
 
-        let (accepts_gzip, accepts_br) = CompressionUtils::accepted_algorithms(request);
-        if !accepts_gzip && !accepts_br {
-            return;
-        }
-
-        if CompressionUtils::already_encoded(response) {
-            return;
-        }
+        let encoding = match CompressionUtils::prepare_compression(request, response, &self.options) {
+            Some(encoding) => encoding,
+            None => return,
+        };
 
-        let content_type = response.content_type();
-        if CompressionUtils::skip_encoding(&content_type, &EXCLUSIONS) {
-            return;
-        }
+        let desired_encoding = CachedEncoding::from(encoding);
 
-        let desired_encoding = if accepts_br {
-            CachedEncoding::Brotli
-        } else {
-            CachedEncoding::Gzip
-        };
-        let encoding = match desired_encoding {
-            CachedEncoding::Gzip => Encoding::Gzip,
-            CachedEncoding::Brotli => Encoding::Brotli,
-        };
+        let cached_body = self.cache.lock().await.get(&(path.clone(), desired_encoding));
 
-        if cache_compressed_responses && (accepts_gzip || accepts_br) {
-            let cached_body = {
-                let guard = CACHED_FILES.read().await;
-                let body = guard.get(&(path.clone(), desired_encoding)).copied();
-                drop(guard);
-                body
-            };
-
-            if let Some(cached_body) = cached_body {
-                debug!("Found cached response for {}", path);
-                response.set_header(Header::new(
-                    CONTENT_ENCODING.as_str(),
-                    format!("{}", encoding),
-                ));
-                response.set_sized_body(cached_body.len(), Cursor::new(cached_body));
-                return;
-            }
+        if let Some(cached_body) = cached_body {
+            debug!("Found cached response for {}", path);
+            response.set_header(Header::new(
+                CONTENT_ENCODING.as_str(),
+                format!("{}", encoding),
+            ));
+            response.set_sized_body(cached_body.len(), Cursor::new(cached_body));
+            return;
         }
 
         let body = response.body_mut().take();
-        let compressed_body: Vec<u8> = match CompressionUtils::compress_body(body, desired_encoding)
-            .await
+        let compressed_body: Vec<u8> = match CompressionUtils::compress_body(
+            body,
+            encoding,
+            self.options.level,
+        )
+        .await
         {
             Ok(compressed_body) => compressed_body,
             Err(err) => {
@@ -229,16 +448,34 @@ impl Fairing for CachedCompression {
             CONTENT_ENCODING.as_str(),
             format!("{}", encoding),
         ));
-        response.set_header(Header::new(
-            CACHE_CONTROL.as_str(),
-            "max-age=31536000"
-        ));
+        if !response.headers().contains(CACHE_CONTROL.as_str()) {
+            response.set_header(Header::new(CACHE_CONTROL.as_str(), "max-age=31536000"));
+        }
+        let compressed_body: Arc<[u8]> = Arc::from(compressed_body);
         response.set_sized_body(compressed_body.len(), Cursor::new(compressed_body.clone()));
 
         debug!("Setting cached response for {}", path);
-        CACHED_FILES
-            .write()
-            .await
-            .insert((path, desired_encoding), Vec::leak(compressed_body));
+        self.cache.lock().await.insert((path, desired_encoding), compressed_body);
+    }
+}
+
+#[cfg(all(test, feature = "gzip"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_past_entry_cap_keeps_total_bytes_bounded() {
+        // Caps small enough that every insert past the second one forces a
+        // capacity eviction, not just a byte-budget eviction.
+        let mut cache = ResponseCache::new(1_000_000, 2);
+
+        for i in 0..10 {
+            let key = (format!("/file-{i}"), CachedEncoding::Gzip);
+            let value: Arc<[u8]> = Arc::from(vec![0u8; 100]);
+            cache.insert(key, value);
+        }
+
+        assert_eq!(cache.entries.len(), 2);
+        assert_eq!(cache.total_bytes, 200);
     }
 }
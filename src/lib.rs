@@ -0,0 +1,451 @@
+//! Compression support for Rocket via
+//! [`async-compression`](https://crates.io/crates/async-compression).
+//!
+//! This crate provides three [fairings](rocket::fairing): [`Compression`] and
+//! [`CachedCompression`], which compress outgoing response bodies with
+//! whichever of Brotli, Zstandard, Gzip or Deflate the client's
+//! `Accept-Encoding` header allows, and [`PrecompressedStatic`], which serves
+//! files precompressed ahead of time instead of compressing them on every
+//! request. Each codec can be disabled independently through its cargo
+//! feature (`brotli`, `zstd`, `gzip`, `deflate`); all four are enabled by
+//! default.
+
+#[macro_use]
+extern crate rocket;
+
+#[cfg(not(any(
+    feature = "brotli",
+    feature = "zstd",
+    feature = "gzip",
+    feature = "deflate"
+)))]
+compile_error!(
+    "rocket_async_compression requires at least one codec feature to be enabled: \
+     `brotli`, `zstd`, `gzip`, or `deflate`"
+);
+
+use std::fmt;
+
+#[cfg(feature = "brotli")]
+use async_compression::tokio::bufread::BrotliEncoder;
+#[cfg(feature = "deflate")]
+use async_compression::tokio::bufread::ZlibEncoder;
+#[cfg(feature = "gzip")]
+use async_compression::tokio::bufread::GzipEncoder;
+#[cfg(feature = "zstd")]
+use async_compression::tokio::bufread::ZstdEncoder;
+
+#[cfg(feature = "brotli")]
+use async_compression::tokio::write::BrotliEncoder as BrotliWriteEncoder;
+#[cfg(feature = "deflate")]
+use async_compression::tokio::write::ZlibEncoder as ZlibWriteEncoder;
+#[cfg(feature = "gzip")]
+use async_compression::tokio::write::GzipEncoder as GzipWriteEncoder;
+#[cfg(feature = "zstd")]
+use async_compression::tokio::write::ZstdEncoder as ZstdWriteEncoder;
+
+use rocket::http::{
+    hyper::header::{CONTENT_ENCODING, VARY},
+    ContentType, Header, MediaType,
+};
+use rocket::tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+use rocket::{Request, Response};
+
+mod fairing;
+mod precompressed;
+
+pub use async_compression::Level;
+pub use fairing::{CachedCompression, CachedCompressionBuilder, Compression, CompressionBuilder};
+pub use precompressed::{PrecompressedStatic, PrecompressedStaticBuilder};
+
+/// The encodings this crate is able to produce, each gated behind its own
+/// cargo feature so that users who only need one codec can trim the rest.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub(crate) enum Encoding {
+    #[cfg(feature = "brotli")]
+    Brotli,
+    #[cfg(feature = "zstd")]
+    Zstd,
+    #[cfg(feature = "gzip")]
+    Gzip,
+    #[cfg(feature = "deflate")]
+    Deflate,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(feature = "brotli")]
+            Encoding::Brotli => write!(f, "br"),
+            #[cfg(feature = "zstd")]
+            Encoding::Zstd => write!(f, "zstd"),
+            #[cfg(feature = "gzip")]
+            Encoding::Gzip => write!(f, "gzip"),
+            #[cfg(feature = "deflate")]
+            Encoding::Deflate => write!(f, "deflate"),
+        }
+    }
+}
+
+/// Per-fairing settings consulted by [`CompressionUtils`] in place of the
+/// crate-wide statics this configuration used to live in, so each
+/// [`Compression`] or [`CachedCompression`] instance can be tuned
+/// independently via its builder.
+pub(crate) struct CompressionOptions {
+    /// Content types left uncompressed.
+    pub(crate) exclusions: Vec<MediaType>,
+    /// Responses with a known body size below this are left uncompressed,
+    /// since small payloads often grow once compressed. Bodies whose size
+    /// isn't known up front are always compressed.
+    pub(crate) min_size: usize,
+    /// Compression level passed through to the underlying codec.
+    pub(crate) level: Level,
+}
+
+/// Shared helpers used by both the [`Compression`] and [`CachedCompression`]
+/// fairings.
+pub(crate) struct CompressionUtils;
+
+impl CompressionUtils {
+    /// The server's fixed preference order, used to break quality-value ties.
+    /// Earlier entries win.
+    const PREFERENCE_ORDER: &'static [Encoding] = &[
+        #[cfg(feature = "brotli")]
+        Encoding::Brotli,
+        #[cfg(feature = "zstd")]
+        Encoding::Zstd,
+        #[cfg(feature = "gzip")]
+        Encoding::Gzip,
+        #[cfg(feature = "deflate")]
+        Encoding::Deflate,
+    ];
+
+    /// Parses a single `coding[;q=value]` entry from an `Accept-Encoding`
+    /// field, returning the lowercased coding name and its quality value
+    /// (defaulting to `1.0`). Unparseable quality values also default to
+    /// `1.0`, matching how most servers treat a malformed `q`.
+    fn parse_coding(entry: &str) -> (String, f32) {
+        let mut parts = entry.split(';');
+        let coding = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+
+        let q = parts
+            .find_map(|param| param.trim().strip_prefix("q="))
+            .and_then(|value| value.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        (coding, q)
+    }
+
+    /// Negotiates the response encoding for `request` per
+    /// [RFC 7231 §5.3.4](https://httpwg.org/specs/rfc7231.html#header.accept-encoding).
+    /// See [`Self::negotiate`] for the actual parsing and selection logic.
+    pub(crate) fn negotiate_encoding(request: &Request<'_>) -> Option<Encoding> {
+        Self::negotiate(request.headers().get_one("Accept-Encoding"))
+    }
+
+    /// Chooses the response encoding for an `Accept-Encoding` header value
+    /// (or its absence) per
+    /// [RFC 7231 §5.3.4](https://httpwg.org/specs/rfc7231.html#header.accept-encoding):
+    /// each entry is parsed as `coding[;q=value]` (default `q=1.0`), `q=0`
+    /// marks a coding as unacceptable, and `*` matches any coding not
+    /// explicitly listed. Among the acceptable codings we can produce, the
+    /// highest quality value wins; ties are broken by [`PREFERENCE_ORDER`].
+    ///
+    /// `identity` only competes with that winner when the client actually
+    /// named it (explicitly or via `*`); a client that never mentions
+    /// identity and only states a sub-1.0 quality for the codings it does
+    /// support (e.g. `gzip;q=0.9`) still gets that coding rather than being
+    /// penalized relative to a client that sent no `Accept-Encoding` at all.
+    /// Returns `None` when identity outranks the winner (or nothing is
+    /// acceptable), meaning the response should be left uncompressed rather
+    /// than treated as an error.
+    ///
+    /// Split out from [`Self::negotiate_encoding`] so the parsing/selection
+    /// logic can be unit-tested without a [`Request`].
+    fn negotiate(header: Option<&str>) -> Option<Encoding> {
+        let header = match header {
+            Some(header) => header,
+            // RFC 7231 §5.3.4: absent header means any content-coding is acceptable.
+            None => return Self::PREFERENCE_ORDER.first().copied(),
+        };
+
+        let entries: Vec<(String, f32)> = header.split(',').map(Self::parse_coding).collect();
+        let wildcard_q = entries
+            .iter()
+            .find(|(coding, _)| coding == "*")
+            .map(|(_, q)| *q);
+
+        let quality_of = |coding: &str| -> f32 {
+            entries
+                .iter()
+                .find(|(c, _)| c == coding)
+                .map(|(_, q)| *q)
+                .or(wildcard_q)
+                .unwrap_or(0.0)
+        };
+
+        // Only treat identity as competing with the winner when the client
+        // actually named it, explicitly or via `*` — never synthesize a
+        // default quality for it the way we do for every other coding.
+        let identity_q = entries
+            .iter()
+            .find(|(c, _)| c == "identity")
+            .map(|(_, q)| *q)
+            .or(wildcard_q);
+
+        // Iterate least-preferred first so that `max_by`, which keeps the
+        // *last* maximum on ties, resolves ties in `PREFERENCE_ORDER`.
+        let best = Self::PREFERENCE_ORDER
+            .iter()
+            .rev()
+            .copied()
+            .map(|encoding| (encoding, quality_of(&encoding.to_string())))
+            .filter(|(_, q)| *q > 0.0)
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+        match best {
+            Some((encoding, q)) if identity_q.is_none_or(|identity_q| q >= identity_q) => {
+                Some(encoding)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if the response already carries a `Content-Encoding`
+    /// header, in which case we should not attempt to compress it again.
+    pub(crate) fn already_encoded(response: &Response<'_>) -> bool {
+        response.headers().contains(CONTENT_ENCODING.as_str())
+    }
+
+    /// Marks the response as varying on `Accept-Encoding`, so that
+    /// shared/CDN caches don't serve a body compressed for one client's
+    /// accepted encodings to another client that accepts different ones.
+    pub(crate) fn add_vary_header(response: &mut Response<'_>) {
+        response.set_header(Header::new(VARY.as_str(), "Accept-Encoding"));
+    }
+
+    /// Returns `true` if `content_type` matches one of `exclusions` and
+    /// should therefore be left uncompressed.
+    pub(crate) fn skip_encoding(content_type: &Option<ContentType>, exclusions: &[MediaType]) -> bool {
+        let content_type = match content_type {
+            Some(content_type) => content_type,
+            None => return false,
+        };
+
+        exclusions
+            .iter()
+            .any(|exclusion| content_type.media_type() == exclusion)
+    }
+
+    /// Returns `true` if `response`'s body is known to be smaller than
+    /// `min_size`. Bodies whose size isn't known up front (e.g. streamed
+    /// bodies) are never skipped, since we can't tell whether they're small.
+    pub(crate) fn below_min_size(response: &Response<'_>, min_size: usize) -> bool {
+        matches!(response.body().preset_size(), Some(size) if size < min_size)
+    }
+
+    /// Runs the gating sequence shared by [`Compression`](super::Compression)
+    /// and [`CachedCompression`](super::CachedCompression) — already-encoded,
+    /// `Vary`, exclusions, minimum size, then negotiation — so both fairings
+    /// apply the same checks in the same order and can't drift apart.
+    /// Returns the negotiated encoding, or `None` if `response` should be
+    /// left as-is for any of those reasons.
+    pub(crate) fn prepare_compression<'r>(
+        request: &'r Request<'_>,
+        response: &mut Response<'r>,
+        options: &CompressionOptions,
+    ) -> Option<Encoding> {
+        if Self::already_encoded(response) {
+            return None;
+        }
+
+        Self::add_vary_header(response);
+
+        let content_type = response.content_type();
+        if Self::skip_encoding(&content_type, &options.exclusions) {
+            return None;
+        }
+
+        if Self::below_min_size(response, options.min_size) {
+            return None;
+        }
+
+        Self::negotiate_encoding(request)
+    }
+
+    /// Compresses `response`'s body in place, streaming it through the
+    /// chosen encoder rather than buffering it up front.
+    pub(crate) fn compress_response<'r>(
+        request: &'r Request<'_>,
+        response: &mut Response<'r>,
+        options: &CompressionOptions,
+    ) {
+        let encoding = match Self::prepare_compression(request, response, options) {
+            Some(encoding) => encoding,
+            None => return,
+        };
+
+        let body = response.body_mut().take();
+        let body = BufReader::new(body);
+        match encoding {
+            #[cfg(feature = "brotli")]
+            Encoding::Brotli => {
+                response.set_streamed_body(BrotliEncoder::with_quality(body, options.level))
+            }
+            #[cfg(feature = "zstd")]
+            Encoding::Zstd => {
+                response.set_streamed_body(ZstdEncoder::with_quality(body, options.level))
+            }
+            #[cfg(feature = "gzip")]
+            Encoding::Gzip => {
+                response.set_streamed_body(GzipEncoder::with_quality(body, options.level))
+            }
+            #[cfg(feature = "deflate")]
+            Encoding::Deflate => {
+                // HTTP's `deflate` coding is actually zlib-wrapped (RFC 1950),
+                // not raw DEFLATE (RFC 1951), so this reuses the zlib codec.
+                response.set_streamed_body(ZlibEncoder::with_quality(body, options.level))
+            }
+        }
+
+        response.set_header(Header::new(CONTENT_ENCODING.as_str(), format!("{}", encoding)));
+    }
+
+    /// Reads `body` to completion through `encoding`'s encoder at the given
+    /// `level`, returning the fully compressed bytes. Used by
+    /// [`CachedCompression`], which needs the whole body up front in order
+    /// to cache it.
+    pub(crate) async fn compress_body(
+        body: rocket::response::Body<'_>,
+        encoding: Encoding,
+        level: Level,
+    ) -> std::io::Result<Vec<u8>> {
+        let mut body = body;
+        let mut buf = Vec::new();
+        body.read_to_end(&mut buf).await?;
+
+        let mut compressed = Vec::new();
+        match encoding {
+            #[cfg(feature = "brotli")]
+            Encoding::Brotli => {
+                let mut encoder = BrotliWriteEncoder::with_quality(&mut compressed, level);
+                encoder.write_all(&buf).await?;
+                encoder.shutdown().await?;
+            }
+            #[cfg(feature = "zstd")]
+            Encoding::Zstd => {
+                let mut encoder = ZstdWriteEncoder::with_quality(&mut compressed, level);
+                encoder.write_all(&buf).await?;
+                encoder.shutdown().await?;
+            }
+            #[cfg(feature = "gzip")]
+            Encoding::Gzip => {
+                let mut encoder = GzipWriteEncoder::with_quality(&mut compressed, level);
+                encoder.write_all(&buf).await?;
+                encoder.shutdown().await?;
+            }
+            #[cfg(feature = "deflate")]
+            Encoding::Deflate => {
+                let mut encoder = ZlibWriteEncoder::with_quality(&mut compressed, level);
+                encoder.write_all(&buf).await?;
+                encoder.shutdown().await?;
+            }
+        }
+
+        Ok(compressed)
+    }
+}
+
+// Exercises `PREFERENCE_ORDER` ties, so these assume all four codecs (the
+// default feature set) are enabled.
+#[cfg(all(test, feature = "brotli", feature = "zstd", feature = "gzip", feature = "deflate"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn absent_header_prefers_most_preferred_encoding() {
+        assert_eq!(CompressionUtils::negotiate(None), Some(Encoding::Brotli));
+    }
+
+    #[test]
+    fn q_zero_excludes_a_coding() {
+        // The only mentioned coding is explicitly excluded; nothing else is
+        // offered, so the response is left uncompressed.
+        assert_eq!(CompressionUtils::negotiate(Some("gzip;q=0")), None);
+
+        // Excluding one coding doesn't stop another from being chosen.
+        assert_eq!(
+            CompressionUtils::negotiate(Some("gzip;q=0, deflate;q=0.5")),
+            Some(Encoding::Deflate)
+        );
+    }
+
+    #[test]
+    fn sub_one_quality_on_the_only_supported_coding_still_compresses() {
+        // Regression test: a client naming exactly one codec at less than
+        // q=1.0, and never mentioning identity, used to be negotiated to
+        // `None` because identity implicitly defaulted to q=1.0.
+        assert_eq!(
+            CompressionUtils::negotiate(Some("gzip;q=0.9")),
+            Some(Encoding::Gzip)
+        );
+        assert_eq!(
+            CompressionUtils::negotiate(Some("gzip;q=0.5, deflate;q=0.3")),
+            Some(Encoding::Gzip)
+        );
+    }
+
+    #[test]
+    fn wildcard_without_explicit_identity_is_used_for_every_coding() {
+        assert_eq!(
+            CompressionUtils::negotiate(Some("*;q=0.5")),
+            Some(Encoding::Brotli)
+        );
+    }
+
+    #[test]
+    fn explicit_identity_can_outrank_the_winner() {
+        assert_eq!(
+            CompressionUtils::negotiate(Some("identity;q=0.9, gzip;q=0.5")),
+            None
+        );
+        assert_eq!(
+            CompressionUtils::negotiate(Some("identity;q=0.5, gzip;q=0.9")),
+            Some(Encoding::Gzip)
+        );
+        // A tie between identity and the winner favors compression, same as
+        // ties among the codecs themselves.
+        assert_eq!(
+            CompressionUtils::negotiate(Some("*;q=0.9, gzip;q=0.5")),
+            Some(Encoding::Brotli)
+        );
+    }
+
+    #[test]
+    fn ties_break_via_preference_order() {
+        assert_eq!(
+            CompressionUtils::negotiate(Some("gzip;q=0.5, br;q=0.5")),
+            Some(Encoding::Brotli)
+        );
+        assert_eq!(
+            CompressionUtils::negotiate(Some("deflate;q=0.8, zstd;q=0.8")),
+            Some(Encoding::Zstd)
+        );
+    }
+
+    #[test]
+    fn parse_coding_trims_and_lowercases() {
+        assert_eq!(
+            CompressionUtils::parse_coding(" GZIP ; q=0.250 "),
+            ("gzip".to_string(), 0.25)
+        );
+        assert_eq!(
+            CompressionUtils::parse_coding("br"),
+            ("br".to_string(), 1.0)
+        );
+        assert_eq!(
+            CompressionUtils::parse_coding("br;q=not-a-number"),
+            ("br".to_string(), 1.0)
+        );
+    }
+}
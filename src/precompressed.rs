@@ -0,0 +1,193 @@
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::{
+        hyper::header::{CONTENT_ENCODING, VARY},
+        Header,
+    },
+    tokio::fs::File,
+    Request, Response,
+};
+use std::path::{Path, PathBuf};
+
+use crate::{CompressionOptions, CompressionUtils, Encoding, Level};
+
+/// The file extension conventionally used for a precompressed sibling of
+/// `encoding`, e.g. `app.js` -> `app.js.br`. Codecs without a conventional
+/// sibling extension (currently just Deflate) return `None`, falling back to
+/// live compression.
+fn precompressed_extension(encoding: Encoding) -> Option<&'static str> {
+    match encoding {
+        #[cfg(feature = "brotli")]
+        Encoding::Brotli => Some("br"),
+        #[cfg(feature = "zstd")]
+        Encoding::Zstd => Some("zst"),
+        #[cfg(feature = "gzip")]
+        Encoding::Gzip => Some("gz"),
+        #[cfg(feature = "deflate")]
+        Encoding::Deflate => None,
+    }
+}
+
+/// Serves files from `root` that were already compressed at build time
+/// instead of compressing them on every request.
+///
+/// After negotiating the client's preferred encoding, this fairing checks
+/// whether a sibling file with the matching extension (`.br`, `.gz`, `.zst`)
+/// exists next to the file at `root` + the request path (e.g. `app.js.br`
+/// next to `app.js`), and if so swaps the response body for that file's
+/// bytes. When no precompressed variant is present, it falls back to live
+/// compression via the same logic as [`Compression`](super::Compression).
+///
+/// # Usage
+///
+/// Attach the fairing ahead of (or instead of) [`Compression`](super::Compression),
+/// pointing it at the directory also served by a [`FileServer`](rocket::fs::FileServer):
+///
+/// ```rust,no_run
+///
+/// use rocket::fs::FileServer;
+/// use rocket_async_compression::PrecompressedStatic;
+///
+/// rocket::build()
+///     // ...
+///     .attach(PrecompressedStatic::fairing("static"))
+///     .mount("/", FileServer::from("static"))
+///     // ...
+///     # ;
+///
+/// ```
+pub struct PrecompressedStatic {
+    root: PathBuf,
+    options: CompressionOptions,
+}
+
+impl PrecompressedStatic {
+    /// Returns a fairing that serves precompressed siblings of the files
+    /// under `root`, falling back to live compression with the default
+    /// exclusion list, no minimum size, and the default compression level.
+    pub fn fairing(root: impl Into<PathBuf>) -> PrecompressedStatic {
+        Self::builder(root).build()
+    }
+
+    /// Returns a [`PrecompressedStaticBuilder`] for configuring the
+    /// live-compression fallback's exclusion list, minimum-size threshold,
+    /// and compression level before building the fairing.
+    pub fn builder(root: impl Into<PathBuf>) -> PrecompressedStaticBuilder {
+        PrecompressedStaticBuilder {
+            root: root.into(),
+            options: CompressionOptions::default(),
+        }
+    }
+
+    /// Returns the path of `root` joined with `relative`'s sibling extension
+    /// for `encoding`, e.g. `root/app.js.br`.
+    ///
+    /// `relative` must already be sanitized (e.g. via
+    /// [`Segments::to_path_buf`](rocket::http::uri::Segments::to_path_buf)),
+    /// since it is joined onto `root` verbatim.
+    fn sibling_path(&self, relative: &Path, encoding: Encoding) -> Option<PathBuf> {
+        let extension = precompressed_extension(encoding)?;
+
+        let mut file_name = self.root.join(relative).into_os_string();
+        file_name.push(".");
+        file_name.push(extension);
+        Some(PathBuf::from(file_name))
+    }
+
+    /// Opens `path` and reads its size, returning `None` (rather than an
+    /// error) if the precompressed sibling doesn't exist or can't be read,
+    /// so the caller can fall back to live compression.
+    async fn open_precompressed(path: &Path) -> Option<(File, u64)> {
+        let file = File::open(path).await.ok()?;
+        let size = file.metadata().await.ok()?.len();
+        Some((file, size))
+    }
+}
+
+/// Builds a [`PrecompressedStatic`] fairing with a customized live-compression
+/// fallback.
+///
+/// Created via [`PrecompressedStatic::builder`].
+pub struct PrecompressedStaticBuilder {
+    root: PathBuf,
+    options: CompressionOptions,
+}
+
+impl PrecompressedStaticBuilder {
+    /// Adds `media_type` to the set of content types left uncompressed when
+    /// falling back to live compression.
+    pub fn exclude(mut self, media_type: rocket::http::MediaType) -> Self {
+        self.options.exclusions.push(media_type);
+        self
+    }
+
+    /// Skips live-compression fallback for responses whose body is smaller
+    /// than `bytes`.
+    pub fn min_size(mut self, bytes: usize) -> Self {
+        self.options.min_size = bytes;
+        self
+    }
+
+    /// Sets the compression level used by the live-compression fallback.
+    pub fn level(mut self, level: Level) -> Self {
+        self.options.level = level;
+        self
+    }
+
+    /// Finishes building, returning the configured [`PrecompressedStatic`]
+    /// fairing.
+    pub fn build(self) -> PrecompressedStatic {
+        PrecompressedStatic {
+            root: self.root,
+            options: self.options,
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for PrecompressedStatic {
+    fn info(&self) -> Info {
+        Info {
+            name: "Precompressed static file serving",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        if CompressionUtils::already_encoded(response) {
+            return;
+        }
+
+        response.set_header(Header::new(VARY.as_str(), "Accept-Encoding"));
+
+        let encoding = match CompressionUtils::negotiate_encoding(request) {
+            Some(encoding) => encoding,
+            None => return,
+        };
+
+        let request_path = request.uri().path();
+        let relative = request_path.segments().to_path_buf(false).ok();
+        let sibling = relative
+            .as_deref()
+            .and_then(|relative| self.sibling_path(relative, encoding));
+
+        if let Some(sibling) = sibling {
+            if let Some((file, size)) = Self::open_precompressed(&sibling).await {
+                debug!("Serving precompressed {} for {}", sibling.display(), request_path);
+                response.set_sized_body(size as usize, file);
+                response.set_header(Header::new(
+                    CONTENT_ENCODING.as_str(),
+                    format!("{}", encoding),
+                ));
+                return;
+            }
+        }
+
+        let content_type = response.content_type();
+        if CompressionUtils::skip_encoding(&content_type, &self.options.exclusions) {
+            return;
+        }
+
+        CompressionUtils::compress_response(request, response, &self.options);
+    }
+}